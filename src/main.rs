@@ -28,72 +28,122 @@ enum Command {
     #[clap(verbatim_doc_comment)]
     Reset,
     /// Dump memory to file
+    ///
+    /// With no --address/--length, dumps every region the target's memory-debug
+    /// table describes; with both, dumps that single range.
     #[clap(verbatim_doc_comment)]
     Read {
-        #[clap(long, short, value_parser=clap_num::maybe_hex::<u32>, default_value = SRAM_RUN_BASE)]
-        address: u32,
-        file_name: String,
+        #[clap(long, short, value_parser=clap_num::maybe_hex::<u32>)]
+        address: Option<u32>,
+        #[clap(long, short, value_parser=clap_num::maybe_hex::<u32>)]
+        length: Option<u32>,
+        file_name: Option<String>,
     },
     /// Parse MBN binary file
     #[clap(verbatim_doc_comment)]
     Parse { file_name: String },
     /// Run binary code from file
+    ///
+    /// Sahara has no jump-to-address message; the target executes the image at
+    /// its own entry point once the transfer completes.
     #[clap(verbatim_doc_comment)]
-    Run {
-        #[clap(long, short, value_parser=clap_num::maybe_hex::<u32>, default_value = SRAM_RUN_BASE)]
-        address: u32,
-        file_name: String,
-    },
+    Run { file_name: String },
 }
 
 /// Qualcomm mask ROM loader tool
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Select a device by USB serial number
+    #[clap(long, global = true)]
+    serial: Option<String>,
+    /// Select a device by USB bus-address path (e.g. 001-007)
+    #[clap(long, global = true)]
+    path: Option<String>,
     /// Command to run
     #[command(subcommand)]
     cmd: Command,
 }
 
+/// Open the requested device, printing an actionable error and exiting on failure.
+fn connect_or_exit(serial: Option<&str>, path: Option<&str>) -> protocol::UsbTransport {
+    match protocol::connect(serial, path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Report a transport failure and exit, so the session aborts cleanly instead
+/// of panicking deep inside a half-finished handshake.
+fn run_or_exit(r: std::result::Result<(), protocol::TransportError>) {
+    if let Err(e) = r {
+        eprintln!("transfer aborted: {e}");
+        std::process::exit(1);
+    }
+}
+
 fn main() {
     // Default to log level "info". Otherwise, you get no "regular" logs.
     let env = env_logger::Env::default().default_filter_or("info");
     env_logger::Builder::from_env(env).init();
 
-    let Cli { cmd } = Cli::parse();
+    let Cli { serial, path, cmd } = Cli::parse();
+    let serial = serial.as_deref();
+    let path = path.as_deref();
 
     match cmd {
         Command::Info => {
-            let (i, e_in_addr, e_out_addr) = protocol::connect();
-            let version = protocol::hello(&i, e_in_addr);
-
-            protocol::switch_mode(&i, version, e_in_addr, e_out_addr, protocol::Mode::Command);
-            protocol::info(&i, version, e_in_addr, e_out_addr)
+            let mut t = connect_or_exit(serial, path);
+            run_or_exit((|| {
+                let version = protocol::hello(&mut t)?;
+                protocol::switch_mode(&mut t, version, protocol::Mode::Command)?;
+                protocol::info(&mut t, version)
+            })());
+        }
+        Command::Load {
+            address: _,
+            file_name,
+        } => {
+            let mut t = connect_or_exit(serial, path);
+            run_or_exit((|| {
+                let version = protocol::hello(&mut t)?;
+                protocol::load(&mut t, version, &file_name)
+            })());
+        }
+        Command::Run { file_name } => {
+            let mut t = connect_or_exit(serial, path);
+            run_or_exit((|| {
+                let version = protocol::hello(&mut t)?;
+                protocol::run(&mut t, version, &file_name)
+            })());
         }
         Command::End => {
-            let (i, e_in_addr, e_out_addr) = protocol::connect();
-            let version = protocol::hello(&i, e_in_addr);
-
-            protocol::end(&i, version, e_in_addr, e_out_addr);
+            let mut t = connect_or_exit(serial, path);
+            run_or_exit((|| {
+                let version = protocol::hello(&mut t)?;
+                protocol::end(&mut t, version)
+            })());
         }
         Command::Reset => {
-            let (i, e_in_addr, e_out_addr) = protocol::connect();
-            let version = protocol::hello(&i, e_in_addr);
-
-            protocol::reset(&i, e_in_addr, e_out_addr);
+            let mut t = connect_or_exit(serial, path);
+            run_or_exit((|| {
+                let _version = protocol::hello(&mut t)?;
+                protocol::reset(&mut t)
+            })());
         }
-        Command::Read { address, file_name } => {
-            let (i, e_in_addr, e_out_addr) = protocol::connect();
-            let version = protocol::hello(&i, e_in_addr);
-
-            protocol::switch_mode(
-                &i,
-                version,
-                e_in_addr,
-                e_out_addr,
-                protocol::Mode::MemoryDebug,
-            );
-            protocol::read_mem(&i, version, e_in_addr, e_out_addr, address);
+        Command::Read {
+            address,
+            length,
+            file_name,
+        } => {
+            let mut t = connect_or_exit(serial, path);
+            run_or_exit((|| {
+                let version = protocol::hello(&mut t)?;
+                protocol::read_mem(&mut t, version, address, length, file_name.as_deref())
+            })());
         }
         Command::Parse { file_name } => {
             match mbn::from_elf(file_name.clone()) {
@@ -113,7 +163,5 @@ fn main() {
                 Err(e) => println!("Cannot parse raw hash table segment: {e:#02x?}"),
             };
         }
-        // TODO
-        _ => {}
     }
 }